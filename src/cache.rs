@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::theme::{CacheConfig, Palette};
+
+/// Where cached entries for `url` would live, keyed by a hash of the art URL so
+/// skipping back to a recently-played track hits the same files.
+fn entry_dir(config: &CacheConfig, url: &str) -> PathBuf {
+    let root = config
+        .dir
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| dirs::cache_dir().map(|dir| dir.join("syncavify")))
+        .unwrap_or_else(std::env::temp_dir);
+
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    root.join(format!("{:x}", hasher.finalize()))
+}
+
+fn palette_path(dir: &Path) -> PathBuf {
+    dir.join("palette.json")
+}
+
+fn image_path(dir: &Path) -> PathBuf {
+    dir.join("art")
+}
+
+/// The cached palette for `url`, if caching is enabled and a cache hit exists.
+pub fn load_palette(config: &CacheConfig, url: &str) -> Option<Palette> {
+    if !config.enabled {
+        return None;
+    }
+    let contents = std::fs::read_to_string(palette_path(&entry_dir(config, url))).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// The cached raw image bytes for `url`, if `keep_raw_image` is on and a cache hit
+/// exists.
+pub fn load_raw_image(config: &CacheConfig, url: &str) -> Option<Vec<u8>> {
+    if !config.enabled || !config.keep_raw_image {
+        return None;
+    }
+    std::fs::read(image_path(&entry_dir(config, url))).ok()
+}
+
+/// Persist the computed palette (and, if configured, the raw image bytes) for `url`
+/// so the next time this track comes up we can skip both the download and the
+/// imagequant pass.
+pub fn store(config: &CacheConfig, url: &str, palette: &Palette, raw_image: &[u8]) -> Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let dir = entry_dir(config, url);
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(palette_path(&dir), serde_json::to_string(palette)?)?;
+
+    if config.keep_raw_image {
+        std::fs::write(image_path(&dir), raw_image)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(keep_raw_image: bool) -> (CacheConfig, PathBuf) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let dir = std::env::temp_dir().join(format!("syncavify-cache-test-{id}"));
+        (
+            CacheConfig {
+                enabled: true,
+                dir: Some(dir.to_string_lossy().into_owned()),
+                keep_raw_image,
+            },
+            dir,
+        )
+    }
+
+    fn sample_palette() -> Palette {
+        Palette {
+            background: "000000".to_string(),
+            gradient_1: "FF0000".to_string(),
+            gradient_2: "00FF00".to_string(),
+        }
+    }
+
+    #[test]
+    fn entry_dir_is_stable_per_url_and_distinct_across_urls() {
+        let (config, _dir) = test_config(false);
+        let a1 = entry_dir(&config, "https://example.com/a.jpg");
+        let a2 = entry_dir(&config, "https://example.com/a.jpg");
+        let b = entry_dir(&config, "https://example.com/b.jpg");
+
+        assert_eq!(a1, a2);
+        assert_ne!(a1, b);
+
+        let _ = std::fs::remove_dir_all(&config.dir.unwrap());
+    }
+
+    #[test]
+    fn load_palette_misses_before_any_store() {
+        let (config, dir) = test_config(false);
+        assert!(load_palette(&config, "https://example.com/a.jpg").is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn store_then_load_round_trips_palette_and_raw_image() {
+        let (config, dir) = test_config(true);
+        let url = "https://example.com/a.jpg";
+        let palette = sample_palette();
+        let raw_image = vec![1, 2, 3, 4];
+
+        store(&config, url, &palette, &raw_image).unwrap();
+
+        let loaded = load_palette(&config, url).unwrap();
+        assert_eq!(loaded.background, palette.background);
+        assert_eq!(loaded.gradient_1, palette.gradient_1);
+        assert_eq!(loaded.gradient_2, palette.gradient_2);
+        assert_eq!(load_raw_image(&config, url).unwrap(), raw_image);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn disabled_cache_never_hits_or_writes() {
+        let (mut config, dir) = test_config(true);
+        config.enabled = false;
+        let url = "https://example.com/a.jpg";
+
+        store(&config, url, &sample_palette(), &[1, 2, 3]).unwrap();
+
+        assert!(load_palette(&config, url).is_none());
+        assert!(load_raw_image(&config, url).is_none());
+        assert!(!dir.exists());
+    }
+}
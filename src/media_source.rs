@@ -0,0 +1,181 @@
+use std::{collections::HashMap, process::Stdio, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use tokio::{process::Command, time::sleep};
+use zbus::{fdo::DBusProxy, zvariant::Value, Connection, MatchRule, MessageStream, MessageType, Proxy};
+
+use crate::spotify_api;
+
+/// How often the polling sources ([`PlayerctlSource`], [`RspotifyWebApiSource`])
+/// re-check for a track change. [`MprisEventSource`] doesn't need this since it
+/// blocks on the signal stream instead of being polled.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A backend that can report the currently-playing track's album art URL.
+///
+/// Lets syncavify swap between MPRIS (Linux-only, via D-Bus signals or `playerctl`), the Spotify Web
+/// API (cross-platform, polling-based), and an embedded Spotify Connect session
+/// without the rest of the pipeline caring which one is active.
+#[async_trait]
+pub trait MediaSource {
+    async fn current_art_url(&mut self) -> Option<String>;
+}
+
+/// Pulls `mpris:artUrl` out of an MPRIS `Metadata` property map, whether it came
+/// from a `PropertiesChanged` signal or a direct `GetAll` call.
+fn extract_art_url(properties: &HashMap<String, Value>) -> Option<String> {
+    properties
+        .get("Metadata")
+        .and_then(|metadata| <&zbus::zvariant::Dict>::try_from(metadata).ok())
+        .and_then(|metadata| metadata.get::<str, String>("mpris:artUrl").ok().flatten())
+}
+
+/// Reads the art URL of the first MPRIS player found on the session bus, so a fresh
+/// [`MprisEventSource`] starts themed instead of waiting for the next track change.
+async fn seed_art_url(connection: &Connection) -> Option<String> {
+    let dbus = DBusProxy::new(connection).await.ok()?;
+    let player_name = dbus
+        .list_names()
+        .await
+        .ok()?
+        .into_iter()
+        .find(|name| name.starts_with("org.mpris.MediaPlayer2."))?;
+
+    let player = Proxy::new(
+        connection,
+        player_name,
+        "/org/mpris/MediaPlayer2",
+        "org.freedesktop.DBus.Properties",
+    )
+    .await
+    .ok()?;
+
+    let properties: HashMap<String, Value> = player
+        .call("GetAll", &"org.mpris.MediaPlayer2.Player")
+        .await
+        .ok()?;
+
+    extract_art_url(&properties)
+}
+
+/// Subscribes to MPRIS `PropertiesChanged` signals on `org.mpris.MediaPlayer2.Player`
+/// over the D-Bus session bus and reads `mpris:artUrl` out of the emitted metadata
+/// map. Linux-only, but unlike [`PlayerctlSource`] this blocks on the signal stream
+/// instead of polling, so it only wakes `do_dbus_loop` when a player's metadata
+/// carries an art URL; repeats of the current URL are skipped there.
+pub struct MprisEventSource {
+    stream: MessageStream,
+    /// The first MPRIS player's art URL at construction time, seeded via `GetAll` so a
+    /// track already playing before startup gets themed immediately instead of
+    /// waiting on the next `PropertiesChanged` signal (which may never come, e.g.
+    /// a track that's playing but paused for the rest of the session).
+    pending: Option<String>,
+}
+
+impl MprisEventSource {
+    pub async fn new() -> Result<Self> {
+        let connection = Connection::session().await?;
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .build();
+        connection.add_match_rule(rule).await?;
+
+        let pending = seed_art_url(&connection).await;
+
+        Ok(Self {
+            stream: MessageStream::from(&connection),
+            pending,
+        })
+    }
+}
+
+#[async_trait]
+impl MediaSource for MprisEventSource {
+    async fn current_art_url(&mut self) -> Option<String> {
+        if let Some(url) = self.pending.take() {
+            return Some(url);
+        }
+
+        while let Some(Ok(msg)) = self.stream.next().await {
+            let Ok((interface, changed, _invalidated)) =
+                msg.body::<(String, HashMap<String, Value>, Vec<String>)>()
+            else {
+                continue;
+            };
+
+            if interface != "org.mpris.MediaPlayer2.Player" {
+                continue;
+            }
+
+            let art_url = extract_art_url(&changed);
+            if art_url.is_some() {
+                return art_url;
+            }
+        }
+        None
+    }
+}
+
+/// Shells out to `playerctl` to read the MPRIS `mpris:artUrl` property on each call.
+/// Linux-only, and superseded by the event-driven [`MprisEventSource`] for the
+/// default loop, but kept around as a simple polling fallback.
+pub struct PlayerctlSource;
+
+#[async_trait]
+impl MediaSource for PlayerctlSource {
+    async fn current_art_url(&mut self) -> Option<String> {
+        sleep(POLL_INTERVAL).await;
+
+        let cmd = "playerctl metadata mpris:artUrl 2>/dev/null | sed s/open.spotify.com/i.scdn.co/";
+        let out = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .stdout(Stdio::piped())
+            .output()
+            .await
+            .ok()?;
+
+        let url = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        if url.is_empty() {
+            None
+        } else {
+            Some(url)
+        }
+    }
+}
+
+/// Polls the Spotify Web API for the currently-playing item. Cross-platform: works
+/// anywhere rspotify's OAuth flow works, independent of any local MPRIS player.
+pub struct RspotifyWebApiSource {
+    spotify: rspotify::AuthCodeSpotify,
+}
+
+impl RspotifyWebApiSource {
+    pub async fn new() -> Self {
+        Self {
+            spotify: spotify_api::auth_spotify().await,
+        }
+    }
+}
+
+#[async_trait]
+impl MediaSource for RspotifyWebApiSource {
+    async fn current_art_url(&mut self) -> Option<String> {
+        sleep(POLL_INTERVAL).await;
+
+        spotify_api::get_smallest_img_url(&self.spotify)
+            .await
+            .ok()
+            .flatten()
+    }
+}
+
+// TODO: a `LibrespotSource` so syncavify can react to track changes without any
+// separate player running at all. Needs a librespot `Session` authenticated
+// against the user's account and a way to read its now-playing metadata (either
+// the session's player event stream or the Spotify Connect `PutStateRequest`
+// payload) before it can implement `MediaSource`.
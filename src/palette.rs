@@ -0,0 +1,290 @@
+use anyhow::Result;
+use image::DynamicImage;
+use imagequant::{self, RGBA};
+
+use crate::theme::Palette;
+
+/// A named region of HSL space to hunt for a representative swatch in, modeled on
+/// the target-based swatch selection from Android's Palette library (and the same
+/// approach shalom's album-art theming uses): rather than just picking the
+/// brightest/darkest colors, score every quantized color against how close it sits
+/// to an archetype (how saturated, how light) and how much of the image it covers.
+struct SwatchTarget {
+    name: &'static str,
+    target_saturation: f64,
+    min_saturation: f64,
+    max_saturation: f64,
+    target_luminance: f64,
+    min_luminance: f64,
+    max_luminance: f64,
+}
+
+const WEIGHT_SATURATION: f64 = 3.0;
+const WEIGHT_LUMINANCE: f64 = 6.5;
+const WEIGHT_POPULATION: f64 = 0.5;
+
+const VIBRANT: SwatchTarget = SwatchTarget {
+    name: "Vibrant",
+    target_saturation: 1.0,
+    min_saturation: 0.35,
+    max_saturation: 1.0,
+    target_luminance: 0.5,
+    min_luminance: 0.3,
+    max_luminance: 0.7,
+};
+
+const DARK_VIBRANT: SwatchTarget = SwatchTarget {
+    name: "DarkVibrant",
+    target_saturation: 1.0,
+    min_saturation: 0.35,
+    max_saturation: 1.0,
+    target_luminance: 0.26,
+    min_luminance: 0.0,
+    max_luminance: 0.45,
+};
+
+const LIGHT_VIBRANT: SwatchTarget = SwatchTarget {
+    name: "LightVibrant",
+    target_saturation: 1.0,
+    min_saturation: 0.35,
+    max_saturation: 1.0,
+    target_luminance: 0.74,
+    min_luminance: 0.55,
+    max_luminance: 1.0,
+};
+
+const MUTED: SwatchTarget = SwatchTarget {
+    name: "Muted",
+    target_saturation: 0.3,
+    min_saturation: 0.0,
+    max_saturation: 0.4,
+    target_luminance: 0.5,
+    min_luminance: 0.3,
+    max_luminance: 0.7,
+};
+
+/// A quantized palette color plus how much of the image it covers and its HSL
+/// saturation and lightness, so swatch scoring doesn't have to recompute either.
+struct Swatch {
+    hex: String,
+    saturation: f64,
+    luminance: f64,
+    population: u32,
+}
+
+fn rgb_to_hsl(r: u8, g: u8, b: u8) -> (f64, f64) {
+    let r = r as f64 / 255.0;
+    let g = g as f64 / 255.0;
+    let b = b as f64 / 255.0;
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let luminance = (max + min) / 2.0;
+
+    let saturation = if (max - min).abs() < f64::EPSILON {
+        0.0
+    } else {
+        (max - min) / (1.0 - (2.0 * luminance - 1.0).abs())
+    };
+
+    (saturation, luminance)
+}
+
+/// The highest-scoring swatch (not already claimed by an earlier target) that falls
+/// inside `target`'s window, or `None` if nothing qualifies (e.g. grayscale art has
+/// no vibrant candidates at all).
+fn best_match(swatches: &[Swatch], target: &SwatchTarget, claimed: &[usize]) -> Option<usize> {
+    let max_population = swatches.iter().map(|s| s.population).max().unwrap_or(1).max(1) as f64;
+
+    swatches
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !claimed.contains(i))
+        .filter_map(|(i, swatch)| {
+            if swatch.saturation < target.min_saturation
+                || swatch.saturation > target.max_saturation
+                || swatch.luminance < target.min_luminance
+                || swatch.luminance > target.max_luminance
+            {
+                return None;
+            }
+            let s = WEIGHT_SATURATION * (1.0 - (swatch.saturation - target.target_saturation).abs())
+                + WEIGHT_LUMINANCE * (1.0 - (swatch.luminance - target.target_luminance).abs())
+                + WEIGHT_POPULATION * (swatch.population as f64 / max_population);
+            Some((i, s))
+        })
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+}
+
+/// Quantize `image` down to `num_colors` and pick a background/gradient_1/
+/// gradient_2 palette using vibrancy-based swatch selection, falling back to plain
+/// brightness ordering for any slot whose target window has no candidate.
+pub async fn select_palette(image: DynamicImage, num_colors: i32) -> Result<Palette> {
+    let mut attr = imagequant::Attributes::new();
+    attr.set_max_colors(num_colors);
+    let bmp = image
+        .to_rgba8()
+        .pixels()
+        .map(|p| imagequant::RGBA::new(p.0[0], p.0[1], p.0[2], p.0[3]))
+        .collect::<Vec<RGBA>>();
+    let mut img = attr.new_image(&bmp, image.width() as usize, image.height() as usize, 0.0)?;
+    let mut result = attr.quantize(&img)?;
+    let (raw_palette, pixels) = result.remapped(&mut img)?;
+
+    let mut population = vec![0u32; raw_palette.len()];
+    for &idx in &pixels {
+        population[idx as usize] += 1;
+    }
+
+    let swatches: Vec<Swatch> = raw_palette
+        .iter()
+        .zip(population.iter())
+        .map(|(color, &population)| {
+            let (saturation, luminance) = rgb_to_hsl(color.r, color.g, color.b);
+            Swatch {
+                hex: format!("{:02X}{:02X}{:02X}", color.r, color.g, color.b),
+                saturation,
+                luminance,
+                population,
+            }
+        })
+        .collect();
+
+    let mut claimed = Vec::new();
+    let dark_vibrant = best_match(&swatches, &DARK_VIBRANT, &claimed);
+    if let Some(i) = dark_vibrant {
+        claimed.push(i);
+    }
+    let vibrant = best_match(&swatches, &VIBRANT, &claimed);
+    if let Some(i) = vibrant {
+        claimed.push(i);
+    }
+    let light_vibrant = best_match(&swatches, &LIGHT_VIBRANT, &claimed);
+    if let Some(i) = light_vibrant {
+        claimed.push(i);
+    }
+    let muted = best_match(&swatches, &MUTED, &claimed);
+    if let Some(i) = muted {
+        claimed.push(i);
+    }
+
+    let mut brightness_order: Vec<usize> = (0..swatches.len()).collect();
+    brightness_order.sort_by(|&a, &b| {
+        swatches[a]
+            .luminance
+            .partial_cmp(&swatches[b].luminance)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    // Walks `brightness_order` lazily, skipping anything already claimed at the time
+    // it's consulted (claims happen progressively as each slot is filled below, so
+    // this can't be precomputed into a single static list up front).
+    let mut next_unclaimed = |preferred: Option<usize>, claimed: &mut Vec<usize>| -> usize {
+        if let Some(i) = preferred {
+            return i;
+        }
+        let i = brightness_order
+            .iter()
+            .copied()
+            .find(|i| !claimed.contains(i))
+            .unwrap_or(0);
+        claimed.push(i);
+        i
+    };
+
+    let background = next_unclaimed(dark_vibrant, &mut claimed);
+    let gradient_1 = next_unclaimed(vibrant, &mut claimed);
+    let gradient_2 = next_unclaimed(light_vibrant.or(muted), &mut claimed);
+
+    Ok(Palette {
+        background: swatches[background].hex.clone(),
+        gradient_1: swatches[gradient_1].hex.clone(),
+        gradient_2: swatches[gradient_2].hex.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swatch(saturation: f64, luminance: f64, population: u32) -> Swatch {
+        Swatch {
+            hex: String::new(),
+            saturation,
+            luminance,
+            population,
+        }
+    }
+
+    #[test]
+    fn rgb_to_hsl_known_colors() {
+        assert_eq!(rgb_to_hsl(255, 0, 0), (1.0, 0.5));
+        assert_eq!(rgb_to_hsl(255, 255, 255), (0.0, 1.0));
+        assert_eq!(rgb_to_hsl(0, 0, 0), (0.0, 0.0));
+        assert_eq!(rgb_to_hsl(128, 128, 128), (0.0, 128.0 / 255.0));
+    }
+
+    #[test]
+    fn best_match_prefers_higher_score_within_window() {
+        let swatches = vec![
+            swatch(0.9, 0.5, 10),  // near-perfect Vibrant fit
+            swatch(0.4, 0.3, 100), // inside the window but a worse fit
+        ];
+        let best = best_match(&swatches, &VIBRANT, &[]);
+        assert_eq!(best, Some(0));
+    }
+
+    #[test]
+    fn best_match_skips_claimed_indices() {
+        let swatches = vec![swatch(0.9, 0.5, 10), swatch(0.8, 0.5, 10)];
+        let best = best_match(&swatches, &VIBRANT, &[0]);
+        assert_eq!(best, Some(1));
+    }
+
+    #[test]
+    fn best_match_returns_none_outside_any_windows() {
+        // Grayscale: zero saturation is below every vibrant target's minimum.
+        let swatches = vec![swatch(0.0, 0.5, 10), swatch(0.0, 0.9, 5)];
+        assert_eq!(best_match(&swatches, &VIBRANT, &[]), None);
+        assert_eq!(best_match(&swatches, &DARK_VIBRANT, &[]), None);
+        assert_eq!(best_match(&swatches, &LIGHT_VIBRANT, &[]), None);
+    }
+
+    #[tokio::test]
+    async fn select_palette_falls_back_to_brightness_for_grayscale_art() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        }));
+
+        let palette = select_palette(image, 2).await.unwrap();
+
+        // No swatch is saturated enough to match DarkVibrant, so `background` must
+        // have fallen back to the darkest color by brightness ordering.
+        assert_eq!(palette.background, "000000");
+    }
+
+    #[tokio::test]
+    async fn select_palette_does_not_reuse_a_muted_match_as_a_fallback_slot() {
+        // Black and white are too desaturated for Vibrant/DarkVibrant/LightVibrant,
+        // so `background`/`gradient_1` fall back to brightness ordering, while the
+        // mid-gray is the only candidate inside Muted's window. Regression test for
+        // `muted` not being pushed into `claimed`, which let a brightness-ordering
+        // fallback re-pick the same index `gradient_2` had already claimed via Muted.
+        let colors = [[0u8, 0, 0], [255, 255, 255], [140, 140, 140]];
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(3, 1, |x, _| {
+            let [r, g, b] = colors[x as usize];
+            image::Rgba([r, g, b, 255])
+        }));
+
+        let palette = select_palette(image, 3).await.unwrap();
+
+        assert_ne!(palette.background, palette.gradient_1);
+        assert_ne!(palette.background, palette.gradient_2);
+        assert_ne!(palette.gradient_1, palette.gradient_2);
+    }
+}
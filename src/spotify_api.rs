@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rspotify::{
+    clients::BaseClient, http::HttpError, model::Image, prelude::*, scopes, AuthCodeSpotify,
+    ClientError, Config, Credentials, OAuth,
+};
+use tokio::time::sleep;
+
+/// Default backoff when the Web API returns a 429 without a `Retry-After` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Where the OAuth token is cached between runs so the user isn't re-prompted on
+/// every launch. Overridable via `SYNCAVIFY_TOKEN_CACHE`.
+fn token_cache_path() -> String {
+    std::env::var("SYNCAVIFY_TOKEN_CACHE").unwrap_or(String::from(".cache/syncavify_token.json"))
+}
+
+/// Authenticate against the Spotify Web API, persisting the token to disk so
+/// subsequent launches can silently refresh it instead of re-prompting.
+pub async fn auth_spotify() -> AuthCodeSpotify {
+    let creds: Credentials = Credentials::from_env().unwrap();
+    let oauth: OAuth = OAuth::from_env(scopes!("user-read-currently-playing")).unwrap();
+    let config: Config = Config {
+        token_cached: true,
+        cache_path: token_cache_path().into(),
+        ..Default::default()
+    };
+
+    let spotify: AuthCodeSpotify = AuthCodeSpotify::with_config(creds, oauth, config);
+
+    // `read_token_cache` returns a cached token (even an expired one) if one exists,
+    // which is then silently refreshed, so we only fall back to the interactive OAuth
+    // prompt when there's nothing to reuse.
+    let cached_token = spotify.read_token_cache(true).await.ok().flatten();
+    if let Some(token) = cached_token {
+        *spotify.token.lock().unwrap() = Some(token);
+        let _ = spotify.refresh_token().await;
+    } else {
+        let auth_url: String = spotify.get_authorize_url(false).unwrap();
+        spotify
+            .prompt_for_token(&auth_url)
+            .await
+            .expect("Authentication Failed");
+    }
+
+    spotify
+}
+
+/// An error that can tell us whether it's a rate limit and, if so, how long to wait
+/// before retrying. Kept separate from `ClientError` so `with_rate_limit_backoff`'s
+/// retry logic can be unit-tested without needing to construct real rspotify errors.
+trait RateLimited {
+    /// `Some(duration)` to wait and retry if this is an HTTP 429, `None` otherwise.
+    fn retry_after(&self) -> Option<Duration>;
+}
+
+impl RateLimited for ClientError {
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ClientError::Http(err) => match err.as_ref() {
+                HttpError::StatusCode(response) if response.status() == 429 => Some(
+                    response
+                        .header("retry-after")
+                        .and_then(|s| s.parse::<u64>().ok())
+                        .map(Duration::from_secs)
+                        .unwrap_or(DEFAULT_RETRY_AFTER),
+                ),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Run a Web API call, retrying on HTTP 429 by sleeping for the `Retry-After`
+/// duration the server asked for (or [`DEFAULT_RETRY_AFTER`] when it didn't send one).
+/// Any other error is returned at once.
+async fn with_rate_limit_backoff<T, E, F, Fut>(mut call: F) -> Result<T>
+where
+    E: RateLimited + std::error::Error + Send + Sync + 'static,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+{
+    loop {
+        match call().await {
+            Ok(value) => return Ok(value),
+            Err(err) => match err.retry_after() {
+                Some(duration) => sleep(duration).await,
+                None => return Err(anyhow!(err)),
+            },
+        }
+    }
+}
+
+/// Get the currently-playing track/episode's smallest album art image, retrying
+/// through rate limits instead of panicking on the first transient 429.
+pub async fn get_smallest_img_url(spotify: &AuthCodeSpotify) -> Result<Option<String>> {
+    let response = with_rate_limit_backoff(|| spotify.current_user_playing_item()).await?;
+
+    let Some(context) = response else {
+        return Ok(None);
+    };
+    let Some(playable_item) = context.item else {
+        return Ok(None);
+    };
+
+    let images: Vec<Image> = match playable_item {
+        rspotify::model::PlayableItem::Track(track) => track.album.images,
+        rspotify::model::PlayableItem::Episode(episode) => episode.images,
+    };
+
+    Ok(images
+        .iter()
+        .filter_map(|image: &Image| image.height.map(|height: u32| (height, image.url.clone())))
+        .min_by(|(height1, _), (height2, _)| height1.cmp(height2))
+        .map(|(_, url)| url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::Cell,
+        fmt,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    #[derive(Debug)]
+    struct FakeError(Option<Duration>);
+
+    impl fmt::Display for FakeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "fake error")
+        }
+    }
+
+    impl std::error::Error for FakeError {}
+
+    impl RateLimited for FakeError {
+        fn retry_after(&self) -> Option<Duration> {
+            self.0
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_rate_limit_backoff_sleeps_retry_after_then_retries() {
+        let attempts = AtomicUsize::new(0);
+        let start = tokio::time::Instant::now();
+
+        let result = with_rate_limit_backoff(|| {
+            let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+            async move {
+                if attempt == 0 {
+                    Err(FakeError(Some(Duration::from_secs(30))))
+                } else {
+                    Ok(42)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+        assert_eq!(start.elapsed().as_secs(), 30);
+    }
+
+    #[tokio::test]
+    async fn with_rate_limit_backoff_propagates_non_rate_limit_errors() {
+        let called = Cell::new(false);
+
+        let result: Result<()> = with_rate_limit_backoff(|| {
+            called.set(true);
+            async { Err(FakeError(None)) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert!(called.get());
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::{fs::File, io::AsyncWriteExt, process::Command};
+
+/// The palette slots extracted from album art, as hex strings (no leading `#`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Palette {
+    pub background: String,
+    pub gradient_1: String,
+    pub gradient_2: String,
+}
+
+impl Palette {
+    /// Look up a palette slot by the name used in a target's `placeholders` table.
+    fn slot(&self, name: &str) -> Option<&str> {
+        match name {
+            "background" => Some(&self.background),
+            "gradient_1" => Some(&self.gradient_1),
+            "gradient_2" => Some(&self.gradient_2),
+            _ => None,
+        }
+    }
+}
+
+/// A single config file to push the current palette into, e.g. Cava, waybar, kitty.
+///
+/// Registered in `theme.toml` as a `[[target]]` table. Each `{{placeholder}}` found in
+/// `path` is substituted with the palette slot it's mapped to in `placeholders`.
+#[derive(Debug, Deserialize)]
+pub struct ThemeTarget {
+    pub name: String,
+    pub path: String,
+    pub placeholders: HashMap<String, String>,
+    pub reload_cmd: Option<String>,
+}
+
+/// The artwork/palette cache, configured alongside the theming targets since both
+/// live in the same `theme.toml`.
+#[derive(Debug, Deserialize, Default)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Overrides the default `$XDG_CACHE_HOME/syncavify` (or platform equivalent).
+    pub dir: Option<String>,
+    /// Also keep the raw downloaded image bytes around, so a cache hit survives a
+    /// restart without needing to re-download even once.
+    #[serde(default)]
+    pub keep_raw_image: bool,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ThemeConfig {
+    #[serde(rename = "target", default)]
+    targets: Vec<ThemeTarget>,
+    #[serde(default)]
+    cache: CacheConfig,
+}
+
+/// Everything read out of `theme.toml`: the theming targets and the artwork cache
+/// settings.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub targets: Vec<ThemeTarget>,
+    pub cache: CacheConfig,
+}
+
+/// Load `theme.toml` (defaults to `theme.toml` in the current working directory,
+/// overridable via `SYNCAVIFY_THEME_CONFIG`).
+pub fn load_config() -> Result<Config> {
+    let config_path = std::env::var("SYNCAVIFY_THEME_CONFIG").unwrap_or(String::from("theme.toml"));
+    let config_str = std::fs::read_to_string(&config_path)?;
+    let config: ThemeConfig = toml::from_str(&config_str)?;
+    Ok(Config {
+        targets: config.targets,
+        cache: config.cache,
+    })
+}
+
+/// Substitute every `{{placeholder}}` in `target`'s file with the palette slot it maps
+/// to, then run its reload command (if any). Unknown placeholders are left untouched.
+pub async fn apply_target(target: &ThemeTarget, palette: &Palette) -> Result<()> {
+    let mut contents = std::fs::read_to_string(&target.path)?;
+
+    for (placeholder, slot) in &target.placeholders {
+        if let Some(value) = palette.slot(slot) {
+            contents = contents.replace(&format!("{{{{{}}}}}", placeholder), value);
+        }
+    }
+
+    let mut file = File::create(&target.path).await?;
+    file.write_all(contents.as_bytes()).await?;
+
+    if let Some(cmd) = &target.reload_cmd {
+        Command::new("sh").arg("-c").arg(cmd).spawn()?.wait().await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn temp_path(contents: &str) -> String {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("syncavify-theme-test-{id}"));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn sample_palette() -> Palette {
+        Palette {
+            background: "000000".to_string(),
+            gradient_1: "FF0000".to_string(),
+            gradient_2: "00FF00".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn apply_target_substitutes_known_placeholders_and_leaves_unknown_untouched() {
+        let path = temp_path("bg={{bg}} grad={{grad}} unknown={{nope}}");
+        let target = ThemeTarget {
+            name: "test".to_string(),
+            path: path.clone(),
+            placeholders: HashMap::from([
+                ("bg".to_string(), "background".to_string()),
+                ("grad".to_string(), "gradient_1".to_string()),
+            ]),
+            reload_cmd: None,
+        };
+
+        apply_target(&target, &sample_palette()).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "bg=000000 grad=FF0000 unknown={{nope}}");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}